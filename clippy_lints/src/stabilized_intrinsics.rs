@@ -1,9 +1,13 @@
 use if_chain::if_chain;
-use rustc::hir::{Expr, ExprKind, QPath};
-use rustc::lint::{LateContext, LateLintPass, LintPass};
+use rustc::hir::def::{DefKind, Res};
+use rustc::hir::def_id::DefId;
+use rustc::hir::{Expr, ExprKind, PathSegment, QPath};
+use rustc::lint::{LateContext, LateLintPass, LintPass, Lint};
 use rustc::{declare_lint_pass, declare_tool_lint};
 use rustc::lint::LintArray;
-use crate::utils::{match_path, span_lint};
+use rustc_errors::Applicability;
+use rustc_target::spec::abi::Abi;
+use crate::utils::{snippet_opt, span_lint_and_sugg};
 
 declare_clippy_lint! {
     /// **What it does:** Checks for calls to intrinsics that have stable counterparts.
@@ -26,116 +30,235 @@ declare_clippy_lint! {
     "checks for calls to intrinsics with stable counterparts"
 }
 
-declare_lint_pass!(StabilizedIntrinsics => [STABILIZED_INTRINSICS]);
-
-const STABILIZED_INTRINSIC_NAMES : &[(&str, &str)] = &[
-    ("add_with_oveflow", "`overflowing_add` on integer types"),
-
-    ("atomic_and", "`fetch_and` on std::sync::atomic types"),
-    ("atomic_and_acq", "`fetch_and` on std::sync::atomic types"),
-    ("atomic_and_acqrel", "`fetch_and` on std::sync::atomic types"),
-    ("atomic_and_rel", "`fetch_and` on std::sync::atomic types"),
-    ("atomic_and_relaxed", "`fetch_and` on std::sync::atomic types"),
-
-    ("atomic_cxchg", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_acq", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_acqrel", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_acqrel_failrelaxed", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_failacq", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_failrelaxed", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_rel", "`compare_exchange` on std::sync::atomic types"),
-    ("atomic_cxchg_relaxed", "`compare_exchange` on std::sync::atomic types"),
-
-    ("atomic_cxchgweak", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_acq", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_acq_failrelaxed", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_acqrel", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_acqrel_failrelaxed", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_failacq", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_failrelaxed", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_rel", "`compare_exchange_weak` on std::sync::atomic types"),
-    ("atomic_cxchgweak_relaxed", "`compare_exchange_weak` on std::sync::atomic types"),
-
-
-    ("atomic_load", "`load` on std::sync::atomic types"),
-    ("atomic_load_acq", "`load` on std::sync::atomic types"),
-    ("atomic_load_relaxed", "`load` on std::sync::atomic types"),
-
-    ("atomic_nand", "`fetch_nand` on std::sync::atomic::AtomicBool"),
-    ("atomic_nand_acq", "`fetch_nand` on std::sync::atomic::AtomicBool"),
-    ("atomic_nand_acqrel", "`fetch_nand` on std::sync::atomic::AtomicBool"),
-    ("atomic_nand_rel", "`fetch_nand` on std::sync::atomic::AtomicBool"),
-    ("atomic_nand_relaxed", "`fetch_nand` on std::sync::atomic::AtomicBool"),
-
-    ("atomic_or", "`fetch_or` on std::sync::atomic types"),
-    ("atomic_or_acq", "`fetch_or` on std::sync::atomic types"),
-    ("atomic_or_acqrel", "`fetch_or` on std::sync::atomic types"),
-    ("atomic_or_rel", "`fetch_or` on std::sync::atomic types"),
-    ("atomic_or_relaxed", "`fetch_or` on std::sync::atomic types"),
-
-    ("atomic_store", "`store` on std::sync::atomic types"),
-    ("atomic_store_rel", "`store` on std::sync::atomic types"),
-    ("atomic_store_relaxed", "`store` on std::sync::atomic types"),
-
-    ("atomic_xadd", "`fetch_add` on std::sync::atomic::AtomicBool"),
-    ("atomic_xadd_acq", "`fetch_add` on std::sync::atomic::AtomicBool"),
-    ("atomic_xadd_acqrel", "`fetch_add` on std::sync::atomic::AtomicBool"),
-    ("atomic_xadd_rel", "`fetch_add` on std::sync::atomic::AtomicBool"),
-    ("atomic_xadd_relaxed", "`fetch_add` on std::sync::atomic::AtomicBool"),
-
-    ("atomic_xchg", "`swap` on std::sync::atomic::AtomicBool"),
-    ("atomic_xchg_acq", "`swap` on std::sync::atomic::AtomicBool"),
-    ("atomic_xchg_acqrel", "`swap` on std::sync::atomic::AtomicBool"),
-    ("atomic_xchg_rel", "`swap` on std::sync::atomic::AtomicBool"),
-    ("atomic_xchg_relaxed", "`swap` on std::sync::atomic::AtomicBool"),
-
-    ("atomic_xor", "`fetch_xor` on std::sync::atomic::AtomicBool"),
-    ("atomic_xor_acq", "`fetch_xor` on std::sync::atomic::AtomicBool"),
-    ("atomic_xor_acqrel", "`fetch_xor` on std::sync::atomic::AtomicBool"),
-    ("atomic_xor_rel", "`fetch_xor` on std::sync::atomic::AtomicBool"),
-    ("atomic_xor_relaxed", "`fetch_xor` on std::sync::atomic::AtomicBool"),
-
-    ("atomic_xsub", "`fetch_sub` on std::sync::atomic::AtomicBool"),
-    ("atomic_xsub_acq", "`fetch_sub` on std::sync::atomic::AtomicBool"),
-    ("atomic_xsub_acqrel", "`fetch_sub` on std::sync::atomic::AtomicBool"),
-    ("atomic_xsub_rel", "`fetch_sub` on std::sync::atomic::AtomicBool"),
-    ("atomic_xsub_relaxed", "`fetch_sub` on std::sync::atomic::AtomicBool"),
-
-    ("mul_with_overflow", "`overflowing_mul` on integer types"),
-
-    ("overflowing_add", "`wrapping_add` on integer types"),
-    ("overflowing_mul", "`wrapping_mul` on integer types"),
-
-    ("rotate_left", "`rotate_left` on integer types"),
-    ("rotate_right", "`rotate_right` on integer types"),
-
-    ("saturating_add", "`saturating_add` on integer types"),
-    ("saturating_sub", "`saturating_sub` on integer types"),
-
-    ("sub_with_overflow", "`overflowing_sub` on integer types"),
-
-    ("volatile_load", "`std::ptr::read_volatile`"),
-    ("volatile_store", "`std::ptr::store_volatile`"),
+declare_clippy_lint! {
+    /// **What it does:** Checks for calls to intrinsics that were removed and relocated to
+    /// `std::ptr`, where the stable replacement's pointer-safety requirements (e.g.
+    /// non-null, properly aligned, valid for reads/writes) differ from the intrinsic's.
+    ///
+    /// **Why is this bad?** Swapping in the stable name without re-checking the surrounding
+    /// `unsafe` reasoning can silently change what preconditions the call relies on.
+    ///
+    /// **Known problems:** None.
+    ///
+    /// **Example:**
+    /// ```rust
+    /// // Bad
+    /// unsafe { core::intrinsics::copy(src, dst, count) };
+    ///
+    /// // Good
+    /// unsafe { std::ptr::copy(src, dst, count) };
+    /// ```
+    pub STABILIZED_PTR_INTRINSICS,
+    correctness,
+    "checks for calls to intrinsics relocated to std::ptr with altered pointer-safety semantics"
+}
+
+declare_lint_pass!(StabilizedIntrinsics => [STABILIZED_INTRINSICS, STABILIZED_PTR_INTRINSICS]);
+
+/// How an intrinsic's stabilized replacement is shaped, and therefore how confidently we can
+/// suggest it.
+enum Stabilized {
+    /// A free function with the same argument list, so the callee path can be swapped verbatim.
+    Direct(&'static str),
+    /// Became a method on the intrinsic's first argument. The call site needs to be restructured
+    /// (the first argument becomes the receiver), which we can't do blindly, so we only suggest
+    /// the shape of the replacement.
+    Method(&'static str),
+}
+
+const STABILIZED_INTRINSIC_NAMES : &[(&str, &str, Stabilized, &'static Lint)] = &[
+    ("add_with_overflow", "`overflowing_add` on integer types", Stabilized::Method("overflowing_add"), STABILIZED_INTRINSICS),
+
+    ("mul_with_overflow", "`overflowing_mul` on integer types", Stabilized::Method("overflowing_mul"), STABILIZED_INTRINSICS),
+
+    ("overflowing_add", "`wrapping_add` on integer types", Stabilized::Method("wrapping_add"), STABILIZED_INTRINSICS),
+    ("overflowing_mul", "`wrapping_mul` on integer types", Stabilized::Method("wrapping_mul"), STABILIZED_INTRINSICS),
+
+    ("rotate_left", "`rotate_left` on integer types", Stabilized::Method("rotate_left"), STABILIZED_INTRINSICS),
+    ("rotate_right", "`rotate_right` on integer types", Stabilized::Method("rotate_right"), STABILIZED_INTRINSICS),
+
+    ("saturating_add", "`saturating_add` on integer types", Stabilized::Method("saturating_add"), STABILIZED_INTRINSICS),
+    ("saturating_sub", "`saturating_sub` on integer types", Stabilized::Method("saturating_sub"), STABILIZED_INTRINSICS),
+
+    ("sub_with_overflow", "`overflowing_sub` on integer types", Stabilized::Method("overflowing_sub"), STABILIZED_INTRINSICS),
+
+    ("volatile_load", "`std::ptr::read_volatile`", Stabilized::Direct("std::ptr::read_volatile"), STABILIZED_INTRINSICS),
+    ("volatile_store", "`std::ptr::write_volatile`", Stabilized::Direct("std::ptr::write_volatile"), STABILIZED_INTRINSICS),
 
     // TODO: these didn't have comments in the overview, maybe others don't as well?
-    ("size_of", "`std::mem::size_of`"),
-    ("transmute", "`std::mem::transmute`"),
+    ("size_of", "`std::mem::size_of`", Stabilized::Direct("std::mem::size_of"), STABILIZED_INTRINSICS),
+    ("transmute", "`std::mem::transmute`", Stabilized::Direct("std::mem::transmute"), STABILIZED_INTRINSICS),
+
+    // Trivial renames with no pointer-safety dimension: `align_of` is a plain computation and
+    // `zeroed`'s concern is an uninitialized value, not pointer validity.
+    ("min_align_of", "`std::mem::align_of`", Stabilized::Direct("std::mem::align_of"), STABILIZED_INTRINSICS),
+    ("init", "`std::mem::zeroed`", Stabilized::Direct("std::mem::zeroed"), STABILIZED_INTRINSICS),
+
+    // Relocated to `std::ptr` with pointer-safety requirements (non-null, alignment, validity for
+    // reads/writes, non-aliasing) that changed along the way, so these are gated as `correctness`
+    // rather than `style`.
+    ("drop_in_place", "`std::ptr::drop_in_place`", Stabilized::Direct("std::ptr::drop_in_place"), STABILIZED_PTR_INTRINSICS),
+    ("copy", "`std::ptr::copy`", Stabilized::Direct("std::ptr::copy"), STABILIZED_PTR_INTRINSICS),
+    ("copy_nonoverlapping", "`std::ptr::copy_nonoverlapping`", Stabilized::Direct("std::ptr::copy_nonoverlapping"), STABILIZED_PTR_INTRINSICS),
+    ("write_bytes", "`std::ptr::write_bytes`", Stabilized::Direct("std::ptr::write_bytes"), STABILIZED_PTR_INTRINSICS),
+    ("move_val_init", "`std::ptr::write`", Stabilized::Direct("std::ptr::write"), STABILIZED_PTR_INTRINSICS),
 ];
 
+/// Maps an `atomic_*` ordering suffix (`relaxed`, `acq`, `rel`, `acqrel`, or the empty string for
+/// the unsuffixed, `SeqCst` form) to the `Ordering` variant it was stabilized as.
+fn parse_ordering(suffix: &str) -> Option<&'static str> {
+    Some(match suffix {
+        "" => "SeqCst",
+        "relaxed" => "Relaxed",
+        "acq" => "Acquire",
+        "rel" => "Release",
+        "acqrel" => "AcqRel",
+        _ => return None,
+    })
+}
+
+/// Decomposes an `atomic_*` intrinsic name into the stabilized method call it corresponds to,
+/// e.g. `atomic_cxchg_acqrel_failrelaxed` -> `compare_exchange(.., Ordering::AcqRel,
+/// Ordering::Relaxed)`. Returns `None` for anything that isn't a recognized `atomic_*` intrinsic,
+/// so callers can fall back to the static table.
+fn parse_atomic_intrinsic(ipath: &str) -> Option<String> {
+    let rest = ipath.strip_prefix("atomic_")?;
+
+    // The failure ordering, if any, is tacked on as a `_fail<ordering>` suffix after the success
+    // ordering, e.g. `cxchg_acqrel_failrelaxed`. Split it off before parsing the rest.
+    let (rest, fail_ordering) = match rest.find("_fail") {
+        Some(idx) => (&rest[..idx], Some(parse_ordering(&rest[idx + "_fail".len()..])?)),
+        None => (rest, None),
+    };
+
+    let mut parts = rest.splitn(2, '_');
+    let op = parts.next().unwrap();
+    let ordering = parse_ordering(parts.next().unwrap_or(""))?;
+
+    let method = match op {
+        "load" => "load",
+        "store" => "store",
+        "xchg" => "swap",
+        "xadd" => "fetch_add",
+        "xsub" => "fetch_sub",
+        "and" => "fetch_and",
+        "or" => "fetch_or",
+        "xor" => "fetch_xor",
+        "nand" => "fetch_nand",
+        // The unsigned variants aren't separate methods; the atomic integer type's signedness
+        // already picks between the signed/unsigned intrinsic, so both map to the same method.
+        "max" | "umax" => "fetch_max",
+        "min" | "umin" => "fetch_min",
+        "cxchg" => "compare_exchange",
+        "cxchgweak" => "compare_exchange_weak",
+        _ => return None,
+    };
+
+    Some(match (method, fail_ordering) {
+        ("compare_exchange", Some(fail)) | ("compare_exchange_weak", Some(fail)) => {
+            format!("{}(.., Ordering::{}, Ordering::{})", method, ordering, fail)
+        },
+        ("compare_exchange", None) | ("compare_exchange_weak", None) => {
+            format!("{}(.., Ordering::{}, ..)", method, ordering)
+        },
+        ("load", _) => format!("load(Ordering::{})", ordering),
+        _ => format!("{}(.., Ordering::{})", method, ordering),
+    })
+}
+
+/// Returns the last path segment regardless of which `QPath` variant resolved it, so callers
+/// don't need to special-case `<Type>::method`-style calls.
+fn last_path_segment<'hir>(qpath: &'hir QPath) -> &'hir PathSegment {
+    match qpath {
+        QPath::Resolved(_, path) => path.segments.last().expect("path has at least one segment"),
+        QPath::TypeRelative(_, segment) => segment,
+    }
+}
+
+/// Renders a path segment's turbofish (e.g. `::<String>`) straight from its resolved HIR
+/// `GenericArgs`, so a suggested replacement keeps any explicit generics the caller wrote even
+/// when the intrinsic was reached through a renamed import and the source snippet doesn't
+/// contain its canonical name. Returns `Some("")` when there's no turbofish to begin with, and
+/// `None` only when one is present but its source text couldn't be recovered.
+fn turbofish(cx: &LateContext<'_, '_>, seg: &PathSegment) -> Option<String> {
+    let args = seg.generic_args().args;
+    if args.is_empty() {
+        return Some(String::new());
+    }
+    let rendered: Option<Vec<String>> = args.iter().map(|arg| snippet_opt(cx, arg.span())).collect();
+    rendered.map(|parts| format!("::<{}>", parts.join(", ")))
+}
+
+/// Whether `def_id` names an item defined in the `core::intrinsics` module, regardless of the
+/// path (`core::intrinsics::..`, `std::intrinsics::..`, a renamed `use`, ..) the call site used to
+/// reach it.
+///
+/// `qpath_res` resolves through re-exports to the original `DefId`, so a stable item that merely
+/// re-exports an intrinsic (e.g. `std::mem::transmute` historically was `pub use
+/// intrinsics::transmute`) would land in `core::intrinsics` too. Requiring the `rust-intrinsic`
+/// ABI rules those out: a safe re-export has the Rust ABI, not the intrinsic one.
+fn is_core_intrinsic(cx: &LateContext<'_, '_>, def_id: DefId) -> bool {
+    cx.tcx.def_path_str(def_id).starts_with("core::intrinsics::") && cx.tcx.fn_sig(def_id).abi() == Abi::RustIntrinsic
+}
+
 impl<'a, 'tcx> LateLintPass<'a, 'tcx> for StabilizedIntrinsics {
     fn check_expr(&mut self, cx: &LateContext<'a, 'tcx>, expr: &'tcx Expr) {
         if_chain! {
             if let ExprKind::Call(ref path, ..) = expr.kind;
             if let ExprKind::Path(ref qpath) = path.kind;
-            if let QPath::Resolved(_, ref rpath) = qpath; // TODO: non-resolved missing here, hopefully not neccessary..
+            if let Res::Def(DefKind::Fn, def_id) = cx.tables.qpath_res(qpath, path.hir_id);
+            if is_core_intrinsic(cx, def_id);
             then {
-                for &(ipath, stabilized_msg) in STABILIZED_INTRINSIC_NAMES {
-                    if match_path(rpath, &["intrinsics", ipath]) {
-                        span_lint(
-                            cx,
-                            STABILIZED_INTRINSICS,
-                            expr.span,
-                            &format!("`{}` is stabilized as {}", ipath, stabilized_msg));
+                let ipath = cx.tcx.item_name(def_id).to_string();
+                let ipath = ipath.as_str();
+                if let Some(replacement) = parse_atomic_intrinsic(ipath) {
+                    span_lint_and_sugg(
+                        cx,
+                        STABILIZED_INTRINSICS,
+                        expr.span,
+                        &format!("`{}` is stabilized as `{}`", ipath, replacement),
+                        "try",
+                        format!("<atomic>.{}", replacement),
+                        Applicability::HasPlaceholders,
+                    );
+                } else if let Some(&(_, stabilized_msg, ref replacement, lint)) =
+                    STABILIZED_INTRINSIC_NAMES.iter().find(|&&(name, _, _, _)| name == ipath)
+                {
+                    let msg = format!("`{}` is stabilized as {}", ipath, stabilized_msg);
+                    match replacement {
+                        Stabilized::Direct(replacement_path) => {
+                            let (sugg, applicability) = match turbofish(cx, last_path_segment(qpath)) {
+                                Some(turbofish) => (
+                                    format!("{}{}", replacement_path, turbofish),
+                                    if std::ptr::eq(lint, STABILIZED_PTR_INTRINSICS) {
+                                        // The replacement can carry different pointer-safety
+                                        // requirements than the intrinsic, so don't let `cargo
+                                        // clippy --fix` apply it unattended even though the swap
+                                        // itself is a direct 1:1 rename.
+                                        Applicability::MaybeIncorrect
+                                    } else {
+                                        Applicability::MachineApplicable
+                                    },
+                                ),
+                                // Had a turbofish but couldn't recover its source text; don't
+                                // guess at it.
+                                None => (format!("{}::<..>", replacement_path), Applicability::MaybeIncorrect),
+                            };
+                            span_lint_and_sugg(cx, lint, path.span, &msg, "try", sugg, applicability);
+                        },
+                        Stabilized::Method(method) => {
+                            // The first argument becomes the receiver, so the whole call (not
+                            // just the callee) needs to be replaced.
+                            span_lint_and_sugg(
+                                cx,
+                                lint,
+                                expr.span,
+                                &msg,
+                                "try",
+                                format!("<recv>.{}(..)", method),
+                                Applicability::HasPlaceholders,
+                            );
+                        },
                     }
                 }
             }